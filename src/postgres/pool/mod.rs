@@ -1,6 +1,10 @@
 extern mod extra;
 
-use extra::arc::MutexArc;
+use extra::arc::{Arc, MutexArc};
+use extra::time::precise_time_s;
+use std::comm::stream;
+use std::io::timer::Timer;
+use std::task::spawn;
 
 use super::{PostgresConnection,
             NormalPostgresStatement,
@@ -9,102 +13,638 @@ use super::{PostgresConnection,
             PostgresTransaction};
 use super::types::ToSql;
 
-struct InnerConnectionPool {
+/// A trait for types that know how to create and check connections for a
+/// `GenericConnectionPool`, following r2d2's `ManageConnection` design.
+///
+/// This decouples the pooling logic (creation, hand-out, health checks,
+/// recycling) from any particular kind of connection.
+pub trait ManageConnection<C, E> {
+    /// Attempts to establish a new connection.
+    fn connect(&self) -> Result<C, E>;
+
+    /// Determines if the connection is still usable.
+    fn is_valid(&self, conn: &mut C) -> bool;
+}
+
+/// A `ManageConnection` that establishes and validates `PostgresConnection`s.
+struct PostgresConnectionManager {
     url: ~str,
-    pool: ~[PostgresConnection],
 }
 
-impl InnerConnectionPool {
-    fn new_connection(&mut self) -> Option<PostgresConnectError> {
-        match PostgresConnection::try_connect(self.url) {
-            Ok(conn) => {
-                self.pool.push(conn);
-                None
-            }
-            Err(err) => Some(err)
+impl ManageConnection<PostgresConnection, PostgresConnectError> for PostgresConnectionManager {
+    fn connect(&self) -> Result<PostgresConnection, PostgresConnectError> {
+        PostgresConnection::try_connect(self.url)
+    }
+
+    fn is_valid(&self, conn: &mut PostgresConnection) -> bool {
+        conn.try_update(";", []).is_ok()
+    }
+}
+
+/// An idle connection together with the timestamps needed to age it out.
+struct PooledConn<C> {
+    conn: C,
+    created_at: f64,
+    last_used_at: f64,
+}
+
+struct InnerConnectionPool<C> {
+    min_size: uint,
+    max_size: uint,
+    // a connection is closed rather than reused once it has been open
+    // this long, regardless of how recently it was used
+    max_lifetime: Option<f64>,
+    // a connection is closed rather than reused if it has been sitting
+    // idle in the pool this long
+    max_idle_time: Option<f64>,
+    // connection attempts are retried this many times beyond the first
+    // before the failure is surfaced to the caller
+    retry_max_attempts: uint,
+    // the delay before the first retry; doubles on each subsequent one
+    retry_base_delay: f64,
+    // the retry delay is capped at this many seconds
+    retry_max_delay: f64,
+    pool: ~[PooledConn<C>],
+    // number of connections that exist, whether idle in `pool` or
+    // currently checked out by a `PooledConnection`
+    num_conns: uint,
+}
+
+impl<C> InnerConnectionPool<C> {
+    /// Returns true if `conn` has outlived `max_lifetime` or has been idle
+    /// longer than `max_idle_time`.
+    fn is_expired(&self, conn: &PooledConn<C>) -> bool {
+        let now = precise_time_s();
+
+        match self.max_lifetime {
+            Some(max) if now - conn.created_at >= max => return true,
+            _ => ()
+        }
+
+        match self.max_idle_time {
+            Some(max) if now - conn.last_used_at >= max => return true,
+            _ => ()
+        }
+
+        false
+    }
+
+    /// Pops an idle connection if one is available, or reserves a slot to
+    /// establish a new one if the pool has room to grow. Returns `None` if
+    /// the pool is already at `max_size` and nothing is idle, in which
+    /// case the caller must wait for another task to return or discard a
+    /// connection.
+    ///
+    /// This only ever touches bookkeeping, never the manager, so it's safe
+    /// to call while holding the pool's lock: the real connect/validate
+    /// I/O happens afterwards, outside the lock, in
+    /// `GenericConnectionPool`.
+    fn reserve(&mut self) -> Option<Reservation<C>> {
+        if !self.pool.is_empty() {
+            return Some(Idle(self.pool.pop()));
+        }
+
+        if self.num_conns < self.max_size {
+            self.num_conns += 1;
+            return Some(Connect);
+        }
+
+        None
+    }
+
+    /// Releases a slot that turned out not to hold a usable connection: a
+    /// reserved connect attempt that failed, or an idle connection that
+    /// failed validation or aged out.
+    fn release_slot(&mut self) {
+        self.num_conns -= 1;
+    }
+
+    /// Accounts for a validated connection being returned: keeps it idle
+    /// if there's room under `min_size`, otherwise lets the surplus
+    /// connection go.
+    fn release(&mut self, conn: PooledConn<C>) {
+        if self.pool.len() >= self.min_size {
+            self.num_conns -= 1;
+        } else {
+            self.pool.push(conn);
         }
     }
 }
 
-/// A simple fixed-size Postgres connection pool.
+/// The outcome of `InnerConnectionPool::reserve`.
+enum Reservation<C> {
+    /// An idle connection was popped off the pool; it still needs to be
+    /// validated (and possibly discarded) before use.
+    Idle(PooledConn<C>),
+    /// No idle connection was available, but the pool had room to grow;
+    /// `num_conns` has already been bumped to reserve the slot, so the
+    /// caller must release it (`InnerConnectionPool::release_slot`) if
+    /// establishing the new connection fails.
+    Connect,
+}
+
+/// A generic connection pool that can be shared across tasks.
 ///
-/// It can be shared across tasks.
-#[deriving(Clone)]
-pub struct PostgresConnectionPool {
-    priv pool: MutexArc<InnerConnectionPool>
+/// The pool keeps `min` connections warm and opens new ones on demand, up
+/// to `max`, as callers ask for more than are currently idle. It is
+/// parameterized over a `ManageConnection` so that the health-check,
+/// recycling and growth logic lives in one place rather than being
+/// hardwired to any one backend.
+pub struct GenericConnectionPool<C, E, M> {
+    priv manager: Arc<M>,
+    priv pool: MutexArc<InnerConnectionPool<C>>,
 }
 
-impl PostgresConnectionPool {
-    /// Attempts to create a new pool with the specified number of connections.
-    ///
-    /// Returns an error if the specified number of connections cannot be
-    /// created.
-    pub fn try_new(url: &str, pool_size: uint)
-            -> Result<PostgresConnectionPool, PostgresConnectError> {
-        let mut pool = InnerConnectionPool {
-            url: url.to_owned(),
-            pool: ~[],
+impl<C, E, M> Clone for GenericConnectionPool<C, E, M> {
+    fn clone(&self) -> GenericConnectionPool<C, E, M> {
+        // `Arc::clone` and `MutexArc::clone` are both cheap refcount
+        // bumps that don't require `C`, `E` or `M` to be `Clone`
+        // themselves, unlike a derived `Clone` impl would demand -- and
+        // `PostgresConnection` (an owned, single-socket resource) and
+        // `PostgresConnectError` have no reason to be.
+        GenericConnectionPool {
+            manager: self.manager.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<C: Send, E: Send, M: ManageConnection<C, E> + Send> GenericConnectionPool<C, E, M> {
+    /// Validates a popped idle connection outside the pool's lock, since
+    /// validation is a real round-trip to the backend. Releases its slot
+    /// and returns `None` if it's dead or has aged out; otherwise returns
+    /// it ready for use.
+    fn validate(&self, mut candidate: PooledConn<C>) -> Option<PooledConn<C>> {
+        let expired = unsafe {
+            do self.pool.unsafe_access |pool| { pool.is_expired(&candidate) }
         };
 
-        while pool.pool.len() < pool_size {
-            match pool.new_connection() {
-                None => (),
-                Some(err) => return Err(err)
+        if expired || !self.manager.get().is_valid(&mut candidate.conn) {
+            self.release_slot();
+            None
+        } else {
+            Some(candidate)
+        }
+    }
+
+    /// Releases a slot reserved by `InnerConnectionPool::reserve` that
+    /// didn't pan out (a failed connect attempt, or a dead/expired idle
+    /// connection), waking any task blocked in `get_connection`'s
+    /// `cvar.wait()` since capacity just freed up.
+    fn release_slot(&self) {
+        unsafe {
+            do self.pool.unsafe_access_cond |pool, cvar| {
+                pool.release_slot();
+                cvar.signal();
             }
         }
+    }
 
-        Ok(PostgresConnectionPool {
-            pool: MutexArc::new(pool)
-        })
+    /// Accounts for a connection being returned by a dropped
+    /// `PooledConnection`: `valid` should already reflect an `is_valid`
+    /// check performed outside the lock. Either puts the connection back
+    /// in the idle pool or releases its slot, then wakes any `cvar.wait()`
+    /// waiter since either outcome frees up capacity for them to notice.
+    fn return_connection(&self, conn: PooledConn<C>, valid: bool) {
+        unsafe {
+            do self.pool.unsafe_access_cond |pool, cvar| {
+                if !valid || pool.is_expired(&conn) {
+                    pool.release_slot();
+                } else {
+                    pool.release(conn);
+                }
+
+                cvar.signal();
+            }
+        }
     }
 
-    /// A convenience function wrapping `try_new`.
+    /// Attempts to establish a connection on its own task, racing it
+    /// against `deadline`.
     ///
-    /// Fails if the pool cannot be created.
-    pub fn new(url: &str, pool_size: uint) -> PostgresConnectionPool {
-        match PostgresConnectionPool::try_new(url, pool_size) {
-            Ok(pool) => pool,
-            Err(err) => fail!("Unable to initialize pool: %s", err.to_str())
+    /// Returns `None` if the deadline passes first. The connect attempt
+    /// itself isn't cancelled -- there's no way to interrupt a
+    /// `ManageConnection` mid-`connect` -- so a pathologically slow one
+    /// keeps running on its own task until it finishes, with its result
+    /// simply discarded.
+    fn connect_by(manager: Arc<M>, deadline: f64) -> Option<Result<C, E>> {
+        let (port, chan) = stream();
+
+        do spawn {
+            chan.send(manager.get().connect());
+        }
+
+        loop {
+            if port.peek() {
+                return Some(port.recv());
+            }
+
+            if precise_time_s() >= deadline {
+                return None;
+            }
+
+            Timer::new().unwrap().sleep(5);
         }
     }
 
     /// Retrieves a connection from the pool.
     ///
-    /// If all connections are in use, blocks until one becomes available.
-    pub fn get_connection(&self) -> PooledPostgresConnection {
-        let conn = unsafe {
-            do self.pool.unsafe_access_cond |pool, cvar| {
-                while pool.pool.is_empty() {
-                    cvar.wait();
+    /// If an idle connection is available it is used (after validation);
+    /// otherwise a new connection is opened as long as the pool is below
+    /// its maximum size. If the pool is already at its maximum, this
+    /// blocks forever until a connection is returned by another task.
+    ///
+    /// A failed connection attempt (e.g. a brief network blip) is retried
+    /// with an exponential backoff rather than surfaced immediately; the
+    /// backoff sleep, and the connect/validate attempts themselves, happen
+    /// without holding the pool's lock so they don't stall other tasks
+    /// returning or fetching connections in the meantime. Once a failed
+    /// attempt has also been retried `retry_max_attempts` times without
+    /// success, this method gives up and fails the task, since it has no
+    /// way to report the error to its caller otherwise.
+    ///
+    /// See `try_get_connection` for a variant that reports the error
+    /// instead of failing the task, and that gives up after a timeout
+    /// instead of blocking indefinitely.
+    pub fn get_connection(&self) -> PooledConnection<C, E, M> {
+        let (mut delay, max_delay, mut retries_left) = unsafe {
+            do self.pool.unsafe_access |pool| {
+                (pool.retry_base_delay, pool.retry_max_delay, pool.retry_max_attempts)
+            }
+        };
+
+        loop {
+            let reservation = unsafe {
+                do self.pool.unsafe_access_cond |pool, cvar| {
+                    let mut reservation;
+                    loop {
+                        match pool.reserve() {
+                            Some(r) => { reservation = r; break; }
+                            None => cvar.wait(),
+                        }
+                    }
+                    reservation
                 }
+            };
+
+            let conn = match reservation {
+                Idle(candidate) => match self.validate(candidate) {
+                    Some(conn) => conn,
+                    None => continue,
+                },
+                Connect => match self.manager.get().connect() {
+                    Ok(c) => {
+                        let now = precise_time_s();
+                        PooledConn { conn: c, created_at: now, last_used_at: now }
+                    }
+                    Err(_) => {
+                        self.release_slot();
+
+                        if retries_left == 0 {
+                            fail!("Unable to establish a pooled connection after retrying");
+                        }
+                        retries_left -= 1;
+
+                        Timer::new().unwrap().sleep((delay * 1000.0) as u64);
+                        delay = if delay * 2.0 < max_delay { delay * 2.0 } else { max_delay };
+                        continue;
+                    }
+                },
+            };
+
+            return PooledConnection {
+                pool: self.clone(),
+                conn: Some(conn.conn),
+                created_at: conn.created_at,
+            };
+        }
+    }
 
-                pool.pool.pop()
+    /// Retrieves a connection from the pool, waiting at most `timeout`
+    /// seconds for one to become available.
+    ///
+    /// Unlike `get_connection`, this will not block forever when the pool
+    /// is exhausted: it polls for an available connection, re-checking the
+    /// elapsed time across each wakeup, and returns `Err(Timeout)` once the
+    /// deadline passes without one becoming available. A new connection is
+    /// established on its own task, raced against the deadline (see
+    /// `connect_by`), so a connect attempt that hangs can't make this
+    /// method overrun `timeout`. A failed connection attempt backs off
+    /// rather than being retried immediately, but still respects the
+    /// overall deadline; if attempts keep failing until either the
+    /// `retry_max_attempts` retries are used up or the backoff reaches the
+    /// deadline, the last error is surfaced as `Err(ConnectFailed(err))`
+    /// instead of a generic timeout.
+    pub fn try_get_connection(&self, timeout: f64)
+            -> Result<PooledConnection<C, E, M>, AcquireError<E>> {
+        let deadline = precise_time_s() + timeout;
+        let (mut delay, max_delay, mut retries_left) = unsafe {
+            do self.pool.unsafe_access |pool| {
+                (pool.retry_base_delay, pool.retry_max_delay, pool.retry_max_attempts)
             }
         };
 
-        PooledPostgresConnection {
-            pool: self.clone(),
-            conn: Some(conn)
+        loop {
+            let reservation = unsafe {
+                do self.pool.unsafe_access |pool| { pool.reserve() }
+            };
+
+            let conn = match reservation {
+                None => {
+                    let remaining = deadline - precise_time_s();
+                    if remaining <= 0.0 {
+                        return Err(Timeout);
+                    }
+                    let sleep_s = if 0.01 < remaining { 0.01 } else { remaining };
+                    Timer::new().unwrap().sleep((sleep_s * 1000.0) as u64);
+                    continue;
+                }
+                Some(Idle(candidate)) => match self.validate(candidate) {
+                    Some(conn) => conn,
+                    None => continue,
+                },
+                Some(Connect) => {
+                    match GenericConnectionPool::connect_by(self.manager.clone(), deadline) {
+                        Some(Ok(c)) => {
+                            let now = precise_time_s();
+                            PooledConn { conn: c, created_at: now, last_used_at: now }
+                        }
+                        Some(Err(err)) => {
+                            self.release_slot();
+
+                            if retries_left == 0 {
+                                return Err(ConnectFailed(err));
+                            }
+                            retries_left -= 1;
+
+                            let remaining = deadline - precise_time_s();
+                            if remaining <= 0.0 {
+                                return Err(ConnectFailed(err));
+                            }
+                            let sleep_s = if delay < remaining { delay } else { remaining };
+                            Timer::new().unwrap().sleep((sleep_s * 1000.0) as u64);
+                            if sleep_s >= remaining {
+                                // The backoff ran up to the deadline, so
+                                // the last connect error is the result.
+                                return Err(ConnectFailed(err));
+                            }
+                            delay = if delay * 2.0 < max_delay { delay * 2.0 } else { max_delay };
+                            continue;
+                        }
+                        None => {
+                            // The connect attempt itself didn't finish
+                            // before the deadline; the slot it reserved is
+                            // released, and the attempt keeps running on
+                            // its own task with its eventual result
+                            // discarded.
+                            self.release_slot();
+                            return Err(Timeout);
+                        }
+                    }
+                }
+            };
+
+            return Ok(PooledConnection {
+                pool: self.clone(),
+                conn: Some(conn.conn),
+                created_at: conn.created_at,
+            });
         }
     }
 }
 
-/// A Postgres connection pulled from a connection pool.
+/// The error returned by `GenericConnectionPool::try_get_connection`.
+pub enum AcquireError<E> {
+    /// No connection became available before the requested timeout
+    /// elapsed.
+    Timeout,
+    /// The pool needed to open a new connection to satisfy the request,
+    /// and the last attempt to do so (after exhausting `retry_max_attempts`
+    /// or the timeout, whichever came first) failed with this error.
+    ConnectFailed(E),
+}
+
+/// Configures and builds a `GenericConnectionPool`.
+pub struct GenericConnectionPoolBuilder<C, E, M> {
+    priv manager: M,
+    priv min_size: uint,
+    priv max_size: uint,
+    priv max_lifetime: Option<f64>,
+    priv max_idle_time: Option<f64>,
+    priv retry_max_attempts: uint,
+    priv retry_base_delay: f64,
+    priv retry_max_delay: f64,
+}
+
+impl<C: Send, E: Send, M: ManageConnection<C, E> + Freeze + Send> GenericConnectionPoolBuilder<C, E, M> {
+    /// Returns a builder wrapping the given connection manager.
+    ///
+    /// The builder defaults to a minimum and maximum of one connection, no
+    /// lifetime or idle limits, and up to 5 retries of a failed connection
+    /// attempt with a backoff starting at 100ms and capped at 5s; call the
+    /// setters below to configure it before `build`ing the pool.
+    pub fn new(manager: M) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder {
+            manager: manager,
+            min_size: 1,
+            max_size: 1,
+            max_lifetime: None,
+            max_idle_time: None,
+            retry_max_attempts: 5,
+            retry_base_delay: 0.1,
+            retry_max_delay: 5.0,
+        }
+    }
+
+    /// Sets the number of connections opened by `build` and kept idle in
+    /// the pool for reuse; a connection returned while this many are
+    /// already idle is closed. A connection discarded as dead or expired
+    /// is not replaced until a caller next asks for one.
+    ///
+    /// Defaults to 1.
+    pub fn min(self, min_size: uint) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { min_size: min_size, ..self }
+    }
+
+    /// Sets the maximum number of connections the pool will open.
+    ///
+    /// Defaults to 1.
+    pub fn max(self, max_size: uint) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { max_size: max_size, ..self }
+    }
+
+    /// Sets the maximum duration in seconds a single physical connection
+    /// may be kept open before it is closed and replaced.
+    ///
+    /// Defaults to no limit.
+    pub fn max_lifetime(self, max_lifetime: f64) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { max_lifetime: Some(max_lifetime), ..self }
+    }
+
+    /// Sets the maximum duration in seconds a connection may sit idle in
+    /// the pool before it is closed and replaced.
+    ///
+    /// Defaults to no limit.
+    pub fn max_idle_time(self, max_idle_time: f64) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { max_idle_time: Some(max_idle_time), ..self }
+    }
+
+    /// Sets how many times a failed connection attempt is retried before
+    /// the error is surfaced to the caller.
+    ///
+    /// Defaults to 5.
+    pub fn retry_max_attempts(self, retry_max_attempts: uint) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { retry_max_attempts: retry_max_attempts, ..self }
+    }
+
+    /// Sets the delay, in seconds, before the first retry of a failed
+    /// connection attempt. The delay doubles on each subsequent retry.
+    ///
+    /// Defaults to 0.1.
+    pub fn retry_base_delay(self, retry_base_delay: f64) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { retry_base_delay: retry_base_delay, ..self }
+    }
+
+    /// Caps the exponential backoff between connection attempt retries, in
+    /// seconds.
+    ///
+    /// Defaults to 5.0.
+    pub fn retry_max_delay(self, retry_max_delay: f64) -> GenericConnectionPoolBuilder<C, E, M> {
+        GenericConnectionPoolBuilder { retry_max_delay: retry_max_delay, ..self }
+    }
+
+    /// Attempts to build the pool, eagerly establishing `min` connections.
+    ///
+    /// Returns an error if those initial connections cannot be created
+    /// even after retrying with backoff.
+    pub fn build(self) -> Result<GenericConnectionPool<C, E, M>, E> {
+        if self.min_size > self.max_size {
+            fail!("min_size (%u) must not be greater than max_size (%u)",
+                  self.min_size, self.max_size);
+        }
+
+        let mut idle = ~[];
+
+        // The pool isn't shared yet, so retrying here with a sleep in
+        // between doesn't block anyone else.
+        while idle.len() < self.min_size {
+            let mut delay = self.retry_base_delay;
+            let mut retries_left = self.retry_max_attempts;
+
+            loop {
+                match self.manager.connect() {
+                    Ok(conn) => {
+                        let now = precise_time_s();
+                        idle.push(PooledConn { conn: conn, created_at: now, last_used_at: now });
+                        break;
+                    }
+                    Err(err) => {
+                        if retries_left == 0 {
+                            return Err(err);
+                        }
+                        retries_left -= 1;
+
+                        Timer::new().unwrap().sleep((delay * 1000.0) as u64);
+                        delay = if delay * 2.0 < self.retry_max_delay {
+                            delay * 2.0
+                        } else {
+                            self.retry_max_delay
+                        };
+                    }
+                }
+            }
+        }
+
+        let num_conns = idle.len();
+        let pool = InnerConnectionPool {
+            min_size: self.min_size,
+            max_size: self.max_size,
+            max_lifetime: self.max_lifetime,
+            max_idle_time: self.max_idle_time,
+            retry_max_attempts: self.retry_max_attempts,
+            retry_base_delay: self.retry_base_delay,
+            retry_max_delay: self.retry_max_delay,
+            pool: idle,
+            num_conns: num_conns,
+        };
+
+        Ok(GenericConnectionPool {
+            manager: Arc::new(self.manager),
+            pool: MutexArc::new(pool),
+        })
+    }
+}
+
+/// A connection pulled from a `GenericConnectionPool`.
 ///
 /// It will be returned to the pool when it falls out of scope, even due to
 /// task failure.
-pub struct PooledPostgresConnection {
-    priv pool: PostgresConnectionPool,
+pub struct PooledConnection<C, E, M> {
+    priv pool: GenericConnectionPool<C, E, M>,
     // TODO remove the Option wrapper when drop takes self by value
-    priv conn: Option<PostgresConnection>
+    priv conn: Option<C>,
+    priv created_at: f64,
 }
 
-impl Drop for PooledPostgresConnection {
+impl<C: Send, E: Send, M: ManageConnection<C, E> + Send> Drop for PooledConnection<C, E, M> {
     fn drop(&mut self) {
-        unsafe {
-            do self.pool.pool.unsafe_access |pool| {
-                pool.pool.push(self.conn.take_unwrap());
-            }
+        let mut conn = PooledConn {
+            conn: self.conn.take_unwrap(),
+            created_at: self.created_at,
+            last_used_at: precise_time_s(),
+        };
+
+        // Validation is a real round-trip to the backend, so it happens
+        // before the pool's lock is taken, not inside it.
+        let valid = self.pool.manager.get().is_valid(&mut conn.conn);
+        self.pool.return_connection(conn, valid);
+    }
+}
+
+/// A Postgres connection pool.
+///
+/// This is a thin instantiation of `GenericConnectionPool` over
+/// `PostgresConnectionManager`; all of the pooling logic lives there.
+pub type PostgresConnectionPool =
+    GenericConnectionPool<PostgresConnection, PostgresConnectError, PostgresConnectionManager>;
+
+/// Configures and builds a `PostgresConnectionPool`.
+pub type PostgresConnectionPoolBuilder =
+    GenericConnectionPoolBuilder<PostgresConnection, PostgresConnectError, PostgresConnectionManager>;
+
+/// A Postgres connection pulled from a connection pool.
+pub type PooledPostgresConnection =
+    PooledConnection<PostgresConnection, PostgresConnectError, PostgresConnectionManager>;
+
+impl PostgresConnectionPool {
+    /// Returns a builder for a pool, starting from the given connection
+    /// string.
+    ///
+    /// The builder defaults to a minimum and maximum of one connection;
+    /// call `min` and/or `max` to size the pool before `build`ing it.
+    pub fn builder(url: &str) -> PostgresConnectionPoolBuilder {
+        GenericConnectionPoolBuilder::new(PostgresConnectionManager { url: url.to_owned() })
+    }
+
+    /// Attempts to create a new pool that opens the specified number of
+    /// connections and never grows beyond it.
+    ///
+    /// Returns an error if the specified number of connections cannot be
+    /// created. This is a convenience wrapper around `builder` that fixes
+    /// `min` and `max` to the same value, preserving the old fixed-size
+    /// behavior.
+    pub fn try_new(url: &str, pool_size: uint)
+            -> Result<PostgresConnectionPool, PostgresConnectError> {
+        PostgresConnectionPool::builder(url).min(pool_size).max(pool_size).build()
+    }
+
+    /// A convenience function wrapping `try_new`.
+    ///
+    /// Fails if the pool cannot be created.
+    pub fn new(url: &str, pool_size: uint) -> PostgresConnectionPool {
+        match PostgresConnectionPool::try_new(url, pool_size) {
+            Ok(pool) => pool,
+            Err(err) => fail!("Unable to initialize pool: %s", err.to_str())
         }
     }
 }
@@ -137,3 +677,209 @@ impl PooledPostgresConnection {
         self.conn.get_ref().in_transaction(blk)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use extra::arc::MutexArc;
+    use std::io::timer::Timer;
+
+    use super::{ManageConnection, GenericConnectionPoolBuilder, Timeout, ConnectFailed};
+
+    struct FakeConnection {
+        id: uint,
+    }
+
+    /// A `ManageConnection` whose `connect` either always succeeds with a
+    /// fresh, uniquely-numbered `FakeConnection`, or always fails, so tests
+    /// can exercise the pool without a real Postgres backend.
+    ///
+    /// `next_id` and `valid` are held behind a `MutexArc` rather than a
+    /// `Cell`, both because the manager needs to be `Freeze` to live in the
+    /// pool's `Arc`, and so tests can flip a live connection dead out from
+    /// under an already-built pool.
+    struct FakeConnectionManager {
+        next_id: MutexArc<uint>,
+        fail: bool,
+        valid: MutexArc<bool>,
+        // lets tests simulate a slow or hanging backend
+        connect_delay_ms: u64,
+    }
+
+    impl FakeConnectionManager {
+        fn new() -> FakeConnectionManager {
+            FakeConnectionManager {
+                next_id: MutexArc::new(0),
+                fail: false,
+                valid: MutexArc::new(true),
+                connect_delay_ms: 0,
+            }
+        }
+
+        fn set_valid(&self, valid: bool) {
+            unsafe {
+                do self.valid.unsafe_access |v| { *v = valid; }
+            }
+        }
+    }
+
+    impl ManageConnection<FakeConnection, ~str> for FakeConnectionManager {
+        fn connect(&self) -> Result<FakeConnection, ~str> {
+            if self.connect_delay_ms > 0 {
+                Timer::new().unwrap().sleep(self.connect_delay_ms);
+            }
+
+            if self.fail {
+                return Err(~"connection refused");
+            }
+            let id = unsafe {
+                do self.next_id.unsafe_access |id| {
+                    let this_id = *id;
+                    *id += 1;
+                    this_id
+                }
+            };
+            Ok(FakeConnection { id: id })
+        }
+
+        fn is_valid(&self, _conn: &mut FakeConnection) -> bool {
+            unsafe {
+                do self.valid.unsafe_access |v| { *v }
+            }
+        }
+    }
+
+    #[test]
+    fn try_get_connection_clamps_backoff_to_the_deadline() {
+        let manager = FakeConnectionManager { fail: true, ..FakeConnectionManager::new() };
+        let pool = GenericConnectionPoolBuilder::new(manager)
+            .min(0)
+            .max(1)
+            .retry_base_delay(1.0)
+            .retry_max_delay(1.0)
+            .build()
+            .unwrap();
+
+        let start = ::extra::time::precise_time_s();
+        match pool.try_get_connection(0.05) {
+            Err(ConnectFailed(_)) => (),
+            Err(Timeout) => fail!("expected the real connect error, not a bare timeout"),
+            Ok(_) => fail!("connect always fails; should never succeed"),
+        }
+        let elapsed = ::extra::time::precise_time_s() - start;
+
+        // The backoff sleeps must be clamped to the deadline rather than
+        // run to completion, or this would take several seconds instead.
+        assert!(elapsed < 0.5);
+    }
+
+    #[test]
+    fn idle_connections_past_max_idle_time_are_replaced() {
+        let manager = FakeConnectionManager::new();
+        let pool = GenericConnectionPoolBuilder::new(manager)
+            .min(1)
+            .max(1)
+            .max_idle_time(0.05)
+            .build()
+            .unwrap();
+
+        let first_id = {
+            let conn = pool.get_connection();
+            conn.conn.get_ref().id
+        };
+
+        // Outlive `max_idle_time` before asking for another connection.
+        Timer::new().unwrap().sleep(100);
+
+        let second_id = pool.get_connection().conn.get_ref().id;
+
+        assert_eq!(first_id, 0);
+        assert_eq!(second_id, 1);
+    }
+
+    #[test]
+    fn dead_idle_connections_are_discarded_and_replaced() {
+        let manager = FakeConnectionManager::new();
+        let pool = GenericConnectionPoolBuilder::new(manager)
+            .min(1)
+            .max(1)
+            .build()
+            .unwrap();
+
+        let first_id = {
+            let conn = pool.get_connection();
+            conn.conn.get_ref().id
+        };
+
+        // Kill validation for the idle connection left behind.
+        pool.manager.get().set_valid(false);
+
+        let second_id = pool.get_connection().conn.get_ref().id;
+
+        assert_eq!(first_id, 0);
+        assert_eq!(second_id, 1);
+    }
+
+    #[test]
+    fn get_connection_wakes_a_blocked_waiter_when_a_connection_is_returned() {
+        let manager = FakeConnectionManager::new();
+        let pool = GenericConnectionPoolBuilder::new(manager)
+            .min(1)
+            .max(1)
+            .build()
+            .unwrap();
+
+        let first = pool.get_connection();
+        let (port, chan) = ::std::comm::stream();
+
+        let waiter_pool = pool.clone();
+        do ::std::task::spawn {
+            let conn = waiter_pool.get_connection();
+            chan.send(conn.conn.get_ref().id);
+        }
+
+        // The waiter should still be blocked: the only connection is
+        // checked out and the pool is already at `max`.
+        assert!(!port.peek());
+
+        drop(first);
+
+        // Dropping `first` must signal the waiter's `cvar.wait()` rather
+        // than leaving it blocked forever.
+        assert_eq!(port.recv(), 0);
+    }
+
+    #[test]
+    fn try_get_connection_respects_the_deadline_even_if_connect_hangs() {
+        let manager = FakeConnectionManager {
+            connect_delay_ms: 500,
+            ..FakeConnectionManager::new()
+        };
+        let pool = GenericConnectionPoolBuilder::new(manager)
+            .min(0)
+            .max(1)
+            .build()
+            .unwrap();
+
+        let start = ::extra::time::precise_time_s();
+        match pool.try_get_connection(0.05) {
+            Err(Timeout) => (),
+            Err(ConnectFailed(_)) => fail!("connect hangs, doesn't fail"),
+            Ok(_) => fail!("connect takes longer than the timeout; should never succeed"),
+        }
+        let elapsed = ::extra::time::precise_time_s() - start;
+
+        // The hanging connect attempt keeps running on its own task, but
+        // this call must return once the deadline passes rather than
+        // waiting for it to finish.
+        assert!(elapsed < 0.2);
+    }
+
+    #[test]
+    #[should_fail]
+    fn build_rejects_min_greater_than_max() {
+        GenericConnectionPoolBuilder::new(FakeConnectionManager::new())
+            .min(2)
+            .max(1)
+            .build();
+    }
+}